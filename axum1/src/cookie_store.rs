@@ -0,0 +1,187 @@
+//! An in-cookie [`SessionStore`] backend, for apps that don't want to run a
+//! Redis/Postgres-backed session store.
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Key as AesKey, Nonce,
+};
+use async_session::{async_trait, base64, Result, Session, SessionStore};
+use axum_extra::extract::cookie::Key;
+use rand::RngCore;
+
+/// Informal cap browsers place on a single cookie's value; exceeding it
+/// risks the client silently truncating or dropping the `SET_COOKIE`.
+const MAX_COOKIE_VALUE_LEN: usize = 4096;
+
+const NONCE_LEN: usize = 12;
+
+/// A [`SessionStore`] that keeps the entire session payload in the cookie
+/// itself, encrypted and authenticated with AES-256-GCM, so the session
+/// doesn't need any server-side storage.
+///
+/// Where [`crate::session::SessionLayer`]'s default signed-cookie path only
+/// authenticates an opaque session id (the data lives in whatever
+/// `SessionStore` is configured), `CookieStore` authenticates *and*
+/// encrypts the full session, trading a hard ~4 KiB size cap for needing
+/// no external store.
+#[derive(Clone)]
+pub struct CookieStore {
+    key: Key,
+}
+
+/// Errors particular to the in-cookie backend, surfaced through
+/// [`SessionStore`]'s `anyhow::Error`-based `Result`.
+#[derive(Debug, thiserror::Error)]
+pub enum CookieStoreError {
+    #[error("encrypted session payload is {0} bytes, which exceeds the {MAX_COOKIE_VALUE_LEN} byte cookie size limit")]
+    PayloadTooLarge(usize),
+    #[error("failed to encrypt session payload")]
+    Encrypt,
+    #[error("failed to decrypt or authenticate session payload")]
+    Decrypt,
+}
+
+impl CookieStore {
+    /// Creates an in-cookie store, deriving its AES-256-GCM key from the
+    /// same master secret used for `SessionLayer`'s cookie signing,
+    /// analogous to how `Key::encryption()` sits alongside `Key::signing()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `secret` is less than 64 bytes, same as `Key::from`.
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            key: Key::from(secret),
+        }
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(AesKey::<Aes256Gcm>::from_slice(&self.key.encryption()[..32]))
+    }
+}
+
+#[async_trait]
+impl SessionStore for CookieStore {
+    async fn load_session(&self, cookie_value: String) -> Result<Option<Session>> {
+        let encoded = match base64::decode(cookie_value) {
+            Ok(encoded) => encoded,
+            Err(_) => return Ok(None),
+        };
+        if encoded.len() < NONCE_LEN {
+            return Ok(None);
+        }
+
+        let (nonce, ciphertext) = encoded.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher()
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| CookieStoreError::Decrypt)?;
+
+        let session: Session = serde_json::from_slice(&plaintext)?;
+        Ok(session.validate())
+    }
+
+    async fn store_session(&self, session: Session) -> Result<Option<String>> {
+        let plaintext = serde_json::to_vec(&session)?;
+
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce_bytes);
+
+        let ciphertext = self
+            .cipher()
+            .encrypt(Nonce::from_slice(&nonce_bytes), plaintext.as_slice())
+            .map_err(|_| CookieStoreError::Encrypt)?;
+
+        let mut payload = nonce_bytes.to_vec();
+        payload.extend_from_slice(&ciphertext);
+
+        let cookie_value = base64::encode(payload);
+        if cookie_value.len() > MAX_COOKIE_VALUE_LEN {
+            return Err(CookieStoreError::PayloadTooLarge(cookie_value.len()).into());
+        }
+
+        Ok(Some(cookie_value))
+    }
+
+    async fn destroy_session(&self, _session: Session) -> Result<()> {
+        // The payload lives entirely in the cookie the client holds; there's
+        // nothing server-side to remove. `SessionLayer` still issues the
+        // removal `SET_COOKIE` for this session.
+        Ok(())
+    }
+
+    async fn clear_store(&self) -> Result<()> {
+        // No server-side storage to clear; see `destroy_session`.
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> CookieStore {
+        CookieStore::new(&[0u8; 64])
+    }
+
+    #[tokio::test]
+    async fn round_trips_a_session_through_the_encrypted_cookie() {
+        let store = store();
+        let mut session = Session::new();
+        session.insert("user_id", 42).unwrap();
+
+        let cookie_value = store.store_session(session).await.unwrap().unwrap();
+        let loaded = store.load_session(cookie_value).await.unwrap().unwrap();
+
+        assert_eq!(loaded.get::<i32>("user_id"), Some(42));
+    }
+
+    #[tokio::test]
+    async fn rejects_a_payload_that_would_exceed_the_cookie_size_limit() {
+        let store = store();
+        let mut session = Session::new();
+        session.insert("blob", "a".repeat(MAX_COOKIE_VALUE_LEN)).unwrap();
+
+        let err = store.store_session(session).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CookieStoreError>(),
+            Some(CookieStoreError::PayloadTooLarge(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_a_cookie_tampered_with_after_encryption() {
+        let store = store();
+        let cookie_value = store
+            .store_session(Session::new())
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Flip a bit in the ciphertext while keeping the base64 well-formed,
+        // so the tamper is caught by GCM's authentication tag rather than
+        // by `base64::decode` simply failing to parse.
+        let mut decoded = base64::decode(cookie_value).unwrap();
+        let last = decoded.len() - 1;
+        decoded[last] ^= 0x01;
+        let tampered = base64::encode(decoded);
+
+        let err = store.load_session(tampered).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CookieStoreError>(),
+            Some(CookieStoreError::Decrypt)
+        ));
+    }
+
+    #[tokio::test]
+    async fn two_stores_with_different_secrets_cannot_read_each_others_cookies() {
+        let cookie_value = store().store_session(Session::new()).await.unwrap().unwrap();
+
+        let other = CookieStore::new(&[1u8; 64]);
+        let err = other.load_session(cookie_value).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CookieStoreError>(),
+            Some(CookieStoreError::Decrypt)
+        ));
+    }
+}
@@ -22,7 +22,7 @@
 
 use std::{
     task::{Context, Poll},
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 use async_session::{
@@ -43,8 +43,21 @@ use axum_extra::extract::cookie::{Cookie, Key, SameSite};
 use futures::future::BoxFuture;
 use tower::{Layer, Service};
 
+use crate::session_ext::CookieOverrides;
+
 const BASE64_DIGEST_LEN: usize = 44;
 
+/// Reserved session keys under which [`SessionLayer::load_or_create`] stamps a
+/// freshly created session, used to enforce `login_deadline` and
+/// `visit_deadline`.
+const LOGIN_TIMESTAMP_KEY: &str = "axum-session.login_timestamp";
+const VISIT_TIMESTAMP_KEY: &str = "axum-session.visit_timestamp";
+
+/// Default granularity at which the visit timestamp is allowed to advance.
+/// Keeping this coarser than "every request" means a read-heavy handler
+/// doesn't force a store write and a fresh `SET_COOKIE` on every call.
+const DEFAULT_VISIT_TIMESTAMP_GRANULARITY: Duration = Duration::from_secs(60);
+
 #[derive(Clone)]
 pub struct SessionLayer<Store> {
     store: Store,
@@ -56,6 +69,11 @@ pub struct SessionLayer<Store> {
     same_site_policy: SameSite,
     secure: Option<bool>,
     key: Key,
+    previous_keys: Vec<Key>,
+    login_deadline: Option<Duration>,
+    visit_deadline: Option<Duration>,
+    visit_timestamp_granularity: Duration,
+    renewal_threshold: Option<Duration>,
 }
 
 impl<Store: SessionStore> SessionLayer<Store> {
@@ -79,6 +97,11 @@ impl<Store: SessionStore> SessionLayer<Store> {
             session_ttl: Some(Duration::from_secs(24 * 60 * 60)),
             secure: None,
             key: Key::from(secret),
+            previous_keys: Vec::new(),
+            login_deadline: None,
+            visit_deadline: None,
+            visit_timestamp_granularity: DEFAULT_VISIT_TIMESTAMP_GRANULARITY,
+            renewal_threshold: None,
         }
     }
 
@@ -128,6 +151,88 @@ impl<Store: SessionStore> SessionLayer<Store> {
         self
     }
 
+    /// Adds keys that cookies are still accepted as valid under, in addition
+    /// to the current signing key. Use this to rotate the server secret
+    /// without logging everyone out: deploy a new secret as the primary
+    /// `key`, keep the old one here for a grace period, and drop it once
+    /// outstanding sessions have cycled. Cookies that verify under a
+    /// previous key are transparently re-signed with the current primary
+    /// key in the response.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any key is less than 64 bytes, same as `SessionLayer::new`.
+    pub fn with_previous_keys<'a>(mut self, previous_keys: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        self.previous_keys = previous_keys.into_iter().map(Key::from).collect();
+        self
+    }
+
+    /// Sets an absolute cap on a session's age, measured from when it was
+    /// first created. Once `now - login_timestamp` exceeds this, the
+    /// session is discarded and a fresh one is built, forcing a new login
+    /// even if the cookie itself hasn't expired. Defaults to `None`
+    /// (no cap).
+    pub fn with_login_deadline(mut self, login_deadline: Option<Duration>) -> Self {
+        self.login_deadline = login_deadline;
+        self
+    }
+
+    /// Sets an idle timeout for the session, measured from the last request
+    /// that used it. Once `now - visit_timestamp` exceeds this, the
+    /// session is discarded and a fresh one is built. Defaults to `None`
+    /// (no idle timeout).
+    pub fn with_visit_deadline(mut self, visit_deadline: Option<Duration>) -> Self {
+        self.visit_deadline = visit_deadline;
+        self
+    }
+
+    /// Sets the granularity at which the visit timestamp is allowed to
+    /// advance. The stamp is only bumped, and the session only persisted
+    /// on its account, once it has fallen at least this far behind `now`;
+    /// this avoids a session store write on every single request. Defaults
+    /// to 60 seconds.
+    ///
+    /// Must stay below `visit_deadline` or an actively-browsing user could
+    /// be logged out: if the stamp can't advance fast enough to stay inside
+    /// the deadline window, a request can arrive to find its own visit
+    /// already "expired". `stamp_timestamps` clamps the effective
+    /// granularity to account for this, but setting a sane value here keeps
+    /// the idle-timeout semantics intentional rather than accidental.
+    pub fn with_visit_timestamp_granularity(mut self, granularity: Duration) -> Self {
+        self.visit_timestamp_granularity = granularity;
+        self
+    }
+
+    /// Sets a renewal threshold: once set, the session is only persisted
+    /// (and a fresh `SET_COOKIE` issued) once its remaining lifetime has
+    /// dropped below `threshold`, or its data actually changed, or it's
+    /// being regenerated, or the request carried no valid session cookie,
+    /// or its cookie verified only under a previous key. A destroyed
+    /// session still gets its removal `SET_COOKIE`. This collapses the common
+    /// read-heavy case to zero store writes while still sliding the
+    /// cookie's expiration forward as it nears `session_ttl`. When unset,
+    /// `save_unchanged` governs this instead. Defaults to `None`.
+    pub fn with_renewal_threshold(mut self, renewal_threshold: Duration) -> Self {
+        self.renewal_threshold = Some(renewal_threshold);
+        self
+    }
+
+    /// Returns `true` if the session's remaining lifetime has dropped below
+    /// `renewal_threshold`, per `with_renewal_threshold`. A session with no
+    /// expiry (no `session_ttl` configured) is always considered due, since
+    /// there's no sliding window to evaluate.
+    fn renewal_due(&self, session: &async_session::Session, threshold: Duration) -> bool {
+        // `Session::expiry` returns a `chrono::DateTime<Utc>`, not a
+        // `std::time::SystemTime`, so the remaining lifetime has to be
+        // computed with `chrono`.
+        let threshold = async_session::chrono::Duration::from_std(threshold)
+            .unwrap_or_else(|_| async_session::chrono::Duration::max_value());
+
+        session
+            .expiry()
+            .map_or(true, |expiry| *expiry - async_session::chrono::Utc::now() < threshold)
+    }
+
     async fn load_or_create(&self, cookie_value: Option<String>) -> crate::session_ext::Session {
         let session = match cookie_value {
             Some(cookie_value) => self.store.load_session(cookie_value).await.ok().flatten(),
@@ -138,22 +243,80 @@ impl<Store: SessionStore> SessionLayer<Store> {
             .and_then(|session| session.validate())
             .unwrap_or_default();
 
+        let inner = if self.login_deadline.is_some() || self.visit_deadline.is_some() {
+            let now = SystemTime::now();
+            let mut inner = if self.deadlines_exceeded(&inner, now) {
+                async_session::Session::new()
+            } else {
+                inner
+            };
+            self.stamp_timestamps(&mut inner, now);
+            inner
+        } else {
+            inner
+        };
+
         crate::session_ext::Session::from_inner(inner)
     }
 
-    fn build_cookie(&self, secure: bool, cookie_value: String) -> Cookie<'static> {
+    /// Returns `true` if the login or visit deadline has elapsed for an
+    /// already-stamped session. A session with no stamps yet (brand new, or
+    /// hydrated from a store that predates these deadlines) is never
+    /// considered expired by this check; it gets stamped fresh instead.
+    fn deadlines_exceeded(&self, session: &async_session::Session, now: SystemTime) -> bool {
+        let expired = |deadline: Option<Duration>, key: &str| {
+            deadline.zip(read_timestamp(session, key)).is_some_and(
+                |(deadline, stamp)| now.duration_since(stamp).unwrap_or_default() > deadline,
+            )
+        };
+
+        expired(self.login_deadline, LOGIN_TIMESTAMP_KEY)
+            || expired(self.visit_deadline, VISIT_TIMESTAMP_KEY)
+    }
+
+    /// Stamps a brand new session with both timestamps, and advances an
+    /// existing session's visit timestamp once it has fallen behind `now`
+    /// by at least the effective granularity (leaving it, and thus
+    /// `data_changed`, untouched otherwise).
+    fn stamp_timestamps(&self, session: &mut async_session::Session, now: SystemTime) {
+        if read_timestamp(session, LOGIN_TIMESTAMP_KEY).is_none() {
+            let _ = session.insert(LOGIN_TIMESTAMP_KEY, now);
+        }
+
+        // Clamp below `visit_deadline`: if the granularity were allowed to
+        // reach (or exceed) the deadline, the stamp couldn't advance fast
+        // enough to keep an actively-browsing user inside the idle-timeout
+        // window, and they'd get logged out mid-session.
+        let granularity = match self.visit_deadline {
+            Some(deadline) => self.visit_timestamp_granularity.min(deadline / 2),
+            None => self.visit_timestamp_granularity,
+        };
+
+        let stale = read_timestamp(session, VISIT_TIMESTAMP_KEY)
+            .map_or(true, |stamp| now.duration_since(stamp).unwrap_or_default() >= granularity);
+        if stale {
+            let _ = session.insert(VISIT_TIMESTAMP_KEY, now);
+        }
+    }
+
+    fn build_cookie(
+        &self,
+        secure: bool,
+        cookie_value: String,
+        overrides: &CookieOverrides,
+    ) -> Cookie<'static> {
         let mut cookie = Cookie::build(self.cookie_name.clone(), cookie_value)
             .http_only(true)
-            .same_site(self.same_site_policy)
-            .secure(secure)
-            .path(self.cookie_path.clone())
+            .same_site(overrides.same_site.unwrap_or(self.same_site_policy))
+            .secure(overrides.secure.unwrap_or(secure))
+            .path(overrides.path.clone().unwrap_or_else(|| self.cookie_path.clone()))
             .finish();
 
-        if let Some(ttl) = self.session_ttl {
+        if let Some(ttl) = overrides.max_age.or(self.session_ttl) {
             cookie.set_expires(Some((std::time::SystemTime::now() + ttl).into()));
         }
 
-        if let Some(cookie_domain) = self.cookie_domain.clone() {
+        if let Some(cookie_domain) = overrides.domain.clone().or_else(|| self.cookie_domain.clone()) {
             cookie.set_domain(cookie_domain)
         }
 
@@ -162,13 +325,23 @@ impl<Store: SessionStore> SessionLayer<Store> {
         cookie
     }
 
-    fn build_removal_cookie(&self, secure: bool) -> Cookie<'static> {
+    fn build_removal_cookie(&self, secure: bool, overrides: &CookieOverrides) -> Cookie<'static> {
         let mut cookie = Cookie::build(self.cookie_name.clone(), "")
             .http_only(true)
-            .same_site(self.same_site_policy)
-            .secure(secure)
+            .same_site(overrides.same_site.unwrap_or(self.same_site_policy))
+            .secure(overrides.secure.unwrap_or(secure))
+            .path(overrides.path.clone().unwrap_or_else(|| self.cookie_path.clone()))
             .finish();
 
+        // A removal cookie only actually deletes the original if its
+        // Path/Domain match those the original was set with; otherwise the
+        // browser treats it as an unrelated cookie at a different scope and
+        // the session cookie a handler scoped via `set_cookie_path`/
+        // `set_cookie_domain` is left behind.
+        if let Some(cookie_domain) = overrides.domain.clone().or_else(|| self.cookie_domain.clone()) {
+            cookie.set_domain(cookie_domain)
+        }
+
         cookie.make_removal();
 
         self.sign_cookie(&mut cookie);
@@ -193,9 +366,10 @@ impl<Store: SessionStore> SessionLayer<Store> {
     // This is mostly based on:
     // https://github.com/SergioBenitez/cookie-rs/blob/master/src/secure/signed.rs#L45-L63
     /// Given a signed value `str` where the signature is prepended to `value`,
-    /// verifies the signed value and returns it. If there's a problem, returns
-    /// an `Err` with a string describing the issue.
-    fn verify_signature(&self, cookie_value: &str) -> Result<String, &'static str> {
+    /// verifies the signed value and returns it along with `true` if a
+    /// previous (non-primary) key was needed to verify it. If there's a
+    /// problem, returns an `Err` with a string describing the issue.
+    fn verify_signature(&self, cookie_value: &str) -> Result<(String, bool), &'static str> {
         if cookie_value.len() < BASE64_DIGEST_LEN {
             return Err("length of value is <= BASE64_DIGEST_LEN");
         }
@@ -204,12 +378,15 @@ impl<Store: SessionStore> SessionLayer<Store> {
         let (digest_str, value) = cookie_value.split_at(BASE64_DIGEST_LEN);
         let digest = base64::decode(digest_str).map_err(|_| "bad base64 digest")?;
 
-        // Perform the verification.
-        let mut mac = Hmac::<Sha256>::new_from_slice(self.key.signing()).expect("a good key");
-        mac.update(value.as_bytes());
-        mac.verify(&digest)
-            .map(|_| value.to_string())
-            .map_err(|_| "value did not verify")
+        std::iter::once(&self.key)
+            .chain(self.previous_keys.iter())
+            .enumerate()
+            .find_map(|(i, key)| {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key.signing()).expect("a good key");
+                mac.update(value.as_bytes());
+                mac.verify(&digest).ok().map(|_| (value.to_string(), i != 0))
+            })
+            .ok_or("value did not verify")
     }
 }
 
@@ -251,7 +428,7 @@ where
             .get(COOKIE)
             .map(|cookies| cookies.to_str());
 
-        let cookie_value = if let Some(Ok(cookies)) = cookie_values {
+        let verified_cookie = if let Some(Ok(cookies)) = cookie_values {
             cookies
                 .split(';')
                 .map(|cookie| cookie.trim())
@@ -262,6 +439,9 @@ where
             None
         };
 
+        let cookie_value = verified_cookie.as_ref().map(|(value, _)| value.clone());
+        let needs_resign = verified_cookie.is_some_and(|(_, needs_resign)| needs_resign);
+
         let secure = self
             .layer
             .secure
@@ -273,14 +453,12 @@ where
         Box::pin(async move {
             let mut session = session_layer.load_or_create(cookie_value.clone()).await;
 
-            if let Some(ttl) = session_layer.session_ttl {
-                session.expire_in(ttl);
-            }
-
             request.extensions_mut().insert(session.clone());
 
             let mut response: Response = ready_service.call(request).await?;
 
+            let overrides = session.cookie_overrides();
+
             if session.is_destroyed() {
                 if let Err(e) = session_layer
                     .store
@@ -291,16 +469,28 @@ where
                     tracing::error!("Failed to destroy session: {:?}", e);
                 }
 
-                let removal_cookie = session_layer.build_removal_cookie(secure);
+                let removal_cookie = session_layer.build_removal_cookie(secure, &overrides);
 
                 response.headers_mut().insert(
                     SET_COOKIE,
                     HeaderValue::from_str(&removal_cookie.to_string()).unwrap(),
                 );
-            } else if session_layer.save_unchanged
-                || session.data_changed()
+            } else if match session_layer.renewal_threshold {
+                Some(threshold) => session_layer.renewal_due(&session, threshold),
+                None => session_layer.save_unchanged,
+            } || session.data_changed()
+                || session.should_regenerate()
                 || cookie_value.is_none()
+                || needs_resign
             {
+                // Slide the expiry forward only once we've decided to
+                // persist: bumping it earlier would make `renewal_due`
+                // above always see `expiry - now == ttl`, never `<
+                // threshold`, so the sliding window could never trigger.
+                if let Some(ttl) = session_layer.session_ttl {
+                    session.expire_in(ttl);
+                }
+
                 if session.should_regenerate() {
                     if let Err(e) = session_layer
                         .store
@@ -317,7 +507,7 @@ where
                     .await
                 {
                     Ok(Some(cookie_value)) => {
-                        let cookie = session_layer.build_cookie(secure, cookie_value);
+                        let cookie = session_layer.build_cookie(secure, cookie_value, &overrides);
                         response.headers_mut().insert(
                             SET_COOKIE,
                             HeaderValue::from_str(&cookie.to_string()).unwrap(),
@@ -335,3 +525,166 @@ where
         })
     }
 }
+
+/// Reads one of the reserved timestamp keys stamped by
+/// [`SessionLayer::stamp_timestamps`], if present.
+fn read_timestamp(session: &async_session::Session, key: &str) -> Option<SystemTime> {
+    session.get::<SystemTime>(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use async_session::MemoryStore;
+
+    use super::*;
+
+    fn layer(secret: &[u8]) -> SessionLayer<MemoryStore> {
+        SessionLayer::new(MemoryStore::new(), secret)
+    }
+
+    #[test]
+    fn verifying_under_the_primary_key_does_not_need_resigning() {
+        let l = layer(&[1u8; 64]);
+        let mut cookie = Cookie::new("axum_sid", "some-session-id");
+        l.sign_cookie(&mut cookie);
+
+        let (value, needs_resign) = l.verify_signature(cookie.value()).unwrap();
+
+        assert_eq!(value, "some-session-id");
+        assert!(!needs_resign);
+    }
+
+    #[test]
+    fn a_cookie_signed_under_a_previous_key_verifies_and_is_flagged_for_resigning() {
+        let old_secret = [1u8; 64];
+        let new_secret = [2u8; 64];
+
+        let mut cookie = Cookie::new("axum_sid", "some-session-id");
+        layer(&old_secret).sign_cookie(&mut cookie);
+
+        let current = layer(&new_secret).with_previous_keys(vec![&old_secret[..]]);
+        let (value, needs_resign) = current.verify_signature(cookie.value()).unwrap();
+
+        assert_eq!(value, "some-session-id");
+        assert!(needs_resign);
+    }
+
+    #[test]
+    fn a_cookie_signed_under_an_unknown_key_does_not_verify() {
+        let mut cookie = Cookie::new("axum_sid", "some-session-id");
+        layer(&[1u8; 64]).sign_cookie(&mut cookie);
+
+        let current = layer(&[2u8; 64]).with_previous_keys(vec![&[3u8; 64][..]]);
+
+        assert!(current.verify_signature(cookie.value()).is_err());
+    }
+
+    #[tokio::test]
+    async fn load_or_create_discards_a_session_past_its_visit_deadline() {
+        let store = MemoryStore::new();
+        let l = SessionLayer::new(store.clone(), &[1u8; 64])
+            .with_visit_deadline(Some(Duration::from_secs(60)));
+
+        let mut stale = async_session::Session::new();
+        stale
+            .insert(LOGIN_TIMESTAMP_KEY, SystemTime::now())
+            .unwrap();
+        stale
+            .insert(
+                VISIT_TIMESTAMP_KEY,
+                SystemTime::now() - Duration::from_secs(3600),
+            )
+            .unwrap();
+        stale.insert("answer", 42).unwrap();
+        let cookie_value = store.store_session(stale.clone()).await.unwrap().unwrap();
+
+        let fresh = l.load_or_create(Some(cookie_value)).await;
+
+        assert_ne!(fresh.id(), stale.id());
+        assert_eq!(fresh.get::<i32>("answer"), None);
+    }
+
+    #[tokio::test]
+    async fn load_or_create_keeps_a_session_within_its_visit_deadline() {
+        let store = MemoryStore::new();
+        let l = SessionLayer::new(store.clone(), &[1u8; 64])
+            .with_visit_deadline(Some(Duration::from_secs(3600)));
+
+        let mut alive = async_session::Session::new();
+        alive
+            .insert(LOGIN_TIMESTAMP_KEY, SystemTime::now())
+            .unwrap();
+        alive
+            .insert(VISIT_TIMESTAMP_KEY, SystemTime::now())
+            .unwrap();
+        alive.insert("answer", 42).unwrap();
+        let cookie_value = store.store_session(alive.clone()).await.unwrap().unwrap();
+
+        let kept = l.load_or_create(Some(cookie_value)).await;
+
+        assert_eq!(kept.id(), alive.id());
+        assert_eq!(kept.get::<i32>("answer"), Some(42));
+    }
+
+    #[tokio::test]
+    async fn load_or_create_does_not_stamp_timestamps_when_no_deadline_is_configured() {
+        let store = MemoryStore::new();
+        let l = SessionLayer::new(store, &[1u8; 64]);
+
+        let fresh = l.load_or_create(None).await;
+
+        assert_eq!(read_timestamp(&fresh, LOGIN_TIMESTAMP_KEY), None);
+        assert_eq!(read_timestamp(&fresh, VISIT_TIMESTAMP_KEY), None);
+    }
+
+    async fn echo(_req: Request<Body>) -> Result<Response, std::convert::Infallible> {
+        Ok(Response::new(Body::empty()))
+    }
+
+    /// Stores `session` and returns a request carrying a validly-signed
+    /// cookie for it, as a client on a later request would send.
+    async fn request_for<Store: SessionStore>(
+        l: &SessionLayer<Store>,
+        store: &Store,
+        session: async_session::Session,
+    ) -> Request<Body> {
+        let cookie_value = store.store_session(session).await.unwrap().unwrap();
+        let mut cookie = Cookie::new(l.cookie_name.clone(), cookie_value);
+        l.sign_cookie(&mut cookie);
+
+        Request::builder()
+            .header(COOKIE, cookie.to_string())
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn renewal_threshold_renews_a_session_nearing_expiry_but_not_a_fresh_one() {
+        let store = MemoryStore::new();
+        let l = SessionLayer::new(store.clone(), &[1u8; 64])
+            .with_session_ttl(Some(Duration::from_secs(3600)))
+            .with_renewal_threshold(Duration::from_secs(60));
+
+        let mut near_expiry = async_session::Session::new();
+        near_expiry.expire_in(Duration::from_secs(30));
+        let near_expiry_request = request_for(&l, &store, near_expiry).await;
+
+        let mut svc = l.layer(tower::service_fn(echo));
+        let response = svc.call(near_expiry_request).await.unwrap();
+        assert!(
+            response.headers().get(SET_COOKIE).is_some(),
+            "a session within the renewal threshold of its expiry should be re-stored and re-issued"
+        );
+
+        let mut far_from_expiry = async_session::Session::new();
+        far_from_expiry.expire_in(Duration::from_secs(3600));
+        let far_from_expiry_request = request_for(&l, &store, far_from_expiry).await;
+
+        let mut svc = l.layer(tower::service_fn(echo));
+        let response = svc.call(far_from_expiry_request).await.unwrap();
+        assert!(
+            response.headers().get(SET_COOKIE).is_none(),
+            "a session far from its expiry should not be re-stored just for being requested"
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! The [`Session`] type inserted into request [`http::Extensions`] by
+//! [`crate::session::SessionLayer`].
+//!
+//! [`http::Extensions`]: axum::http::Extensions
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use async_session::Session as AsyncSession;
+use axum_extra::extract::cookie::SameSite;
+
+/// Per-session overrides for cookie attributes that are otherwise fixed
+/// globally on `SessionLayer`. Unset fields fall back to the layer's
+/// defaults when the response cookie is built.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CookieOverrides {
+    pub(crate) same_site: Option<SameSite>,
+    pub(crate) secure: Option<bool>,
+    pub(crate) path: Option<String>,
+    pub(crate) domain: Option<String>,
+    pub(crate) max_age: Option<Duration>,
+}
+
+/// A handle to the current request's session data.
+///
+/// This wraps [`async_session::Session`], which already shares its data
+/// across clones, and layers on a regeneration flag and cookie attribute
+/// overrides so a handler can influence how the middleware builds the
+/// response cookie without reaching into the session store itself.
+#[derive(Debug, Clone)]
+pub struct Session {
+    inner: AsyncSession,
+    regenerate: Arc<AtomicBool>,
+    cookie_overrides: Arc<RwLock<CookieOverrides>>,
+}
+
+impl Session {
+    pub(crate) fn from_inner(inner: AsyncSession) -> Self {
+        Self {
+            inner,
+            regenerate: Arc::new(AtomicBool::new(false)),
+            cookie_overrides: Arc::new(RwLock::new(CookieOverrides::default())),
+        }
+    }
+
+    pub(crate) fn into_inner(self) -> AsyncSession {
+        self.inner
+    }
+
+    /// Requests that the middleware regenerate this session's id once the
+    /// response is built, preserving the session's data.
+    pub fn regenerate(&self) {
+        self.regenerate.store(true, Ordering::Release);
+    }
+
+    pub(crate) fn should_regenerate(&self) -> bool {
+        self.regenerate.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn inner_regenerate(&mut self) {
+        self.inner.regenerate();
+        self.regenerate.store(false, Ordering::Release);
+    }
+
+    /// Overrides the `SameSite` attribute of this session's cookie, e.g. to
+    /// relax a cross-site OAuth callback to `SameSite::None` while the rest
+    /// of the app stays `Strict`.
+    pub fn set_cookie_same_site(&self, same_site: SameSite) {
+        self.cookie_overrides.write().unwrap().same_site = Some(same_site);
+    }
+
+    /// Overrides the `Secure` attribute of this session's cookie.
+    pub fn set_cookie_secure(&self, secure: bool) {
+        self.cookie_overrides.write().unwrap().secure = Some(secure);
+    }
+
+    /// Overrides the `Path` attribute of this session's cookie.
+    pub fn set_cookie_path(&self, path: impl Into<String>) {
+        self.cookie_overrides.write().unwrap().path = Some(path.into());
+    }
+
+    /// Overrides the `Domain` attribute of this session's cookie.
+    pub fn set_cookie_domain(&self, domain: impl Into<String>) {
+        self.cookie_overrides.write().unwrap().domain = Some(domain.into());
+    }
+
+    /// Overrides this session's cookie expiration, measured from when the
+    /// response is built, in place of `SessionLayer`'s `session_ttl`.
+    pub fn set_cookie_max_age(&self, max_age: Duration) {
+        self.cookie_overrides.write().unwrap().max_age = Some(max_age);
+    }
+
+    pub(crate) fn cookie_overrides(&self) -> CookieOverrides {
+        self.cookie_overrides.read().unwrap().clone()
+    }
+}
+
+impl Deref for Session {
+    type Target = AsyncSession;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl DerefMut for Session {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}